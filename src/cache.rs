@@ -13,15 +13,10 @@
 ///!
 ///! # About pending
 ///!
-///! For now, `writium-cache` doesn't offer any multi-threading features, but it
-///! guarantees that everything won't go wrong in such context. There is only
-///! one thread is involved at a time for a single `Cache` object. Everytime an
-///! uncached item is requested, an cached item needs to be unloaded/removed,
-///! heavy I/O might come up. In such period of time I/O-ing, the item is
-///! attributed 'pending'. Items in such state is not exposed to users. And it
-///! can influence the entire system's efficiency seriously by blocking threads.
-///! Such outcome is undesirable commonly. Thus, 'pending' state is considered a
-///! performance issue and should be fixed in future versions.
+///! `writium-cache` is safe to share across threads. Everytime an uncached
+///! item is requested, an cached item needs to be unloaded/removed, heavy I/O
+///! might come up. In such period of time I/O-ing, the item is attributed
+///! 'pending'. Items in such state is not exposed to users.
 ///!
 ///! There are two cases an item is in 'pending' state:
 ///!
@@ -30,32 +25,158 @@
 ///! local storage. If the data is requested again after this intermediate
 ///! state, the state will be restored to `Intact`. When unloading is
 ///! finished, data is written back to storage and is removed from the owning
-///! `Cache`.
+///! `Cache`. This window is *not* coalesced: a request for the same id that
+///! arrives while the old instance is still being unloaded simply starts its
+///! own fresh load from the `CacheSource`, independent of (and possibly
+///! concurrent with) the in-progress unload.
 ///!
 ///! 2. Cached data is being removed by a corresponding `CacheSource`. If the
 ///! data is requested again after this intermediate state, the state will be
 ///! restored to `Dirty` (as a new instance is created). When removal is
 ///! finished, data is removed from storage (as well as the owning `Cache`, if
-///! the data was loaded).
+///! the data was loaded). Concurrent misses for the same id *are* coalesced
+///! here: only the first caller actually loads from the `CacheSource`, and
+///! every other caller waits for that single load to finish instead of
+///! duplicating the I/O.
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use writium::prelude::*;
+use inflight::{InflightRegistry, InflightSlot};
 use item::CacheItem;
+use metrics::{Metrics, MetricsSnapshot};
+use policy::FrequencySketch;
+use shard::{InsertOutcome, Shard};
 
 const ERR_POISONED_THREAD: &str = "Current thread is poisoned.";
+/// Number of resident items sampled as victim candidates when the TinyLFU
+/// policy needs to evict something.
+const TINY_LFU_SAMPLE_SIZE: usize = 5;
+/// Shard counts above this aren't worth it; `default_shard_count` never
+/// picks more.
+const MAX_SHARDS: usize = 16;
+/// Below this much capacity per shard, per-shard capacity would be too small
+/// for the sampling and recency bookkeeping to behave sensibly, so small
+/// caches stay single-sharded.
+const MIN_CAPACITY_PER_SHARD: usize = 8;
+
+/// Eviction policy selected when constructing a `Cache`.
+pub enum EvictionPolicy {
+    /// Strict recency order: the least recently used item is always evicted
+    /// first. This is the original, default behavior.
+    Lru,
+    /// Frequency-aware admission modeled on TinyLFU. A handful of resident
+    /// items are sampled on eviction and the incoming item only replaces the
+    /// least-used sample if it's estimated to be accessed more often, so a
+    /// single scan can't evict a hot item.
+    TinyLfu,
+}
+
+/// Time-to-live configuration selected when constructing a `Cache`.
+pub enum Ttl {
+    /// Items never expire. This is the original, default behavior.
+    None,
+    /// Items expire `Duration` after they were loaded, regardless of how
+    /// often they're accessed in between.
+    Fixed(Duration),
+    /// Items expire `Duration` after their most recent hit; every hit resets
+    /// the clock, so a hot item never expires.
+    Sliding(Duration),
+}
 
 /// Cache for each Writium Api. Any Writium Api can be composited with this
 /// struct for cache.
 pub struct Cache<T: 'static> {
-    /// In `cache`, the flag `is_dirty` of an item indicates whether it should
-    /// be written back to source.
-    cache: Mutex<Vec<Arc<CacheItem<T>>>>,
+    shards: Vec<Shard<T>>,
+    shard_mask: usize,
+    /// Total cost this cache may hold at once, per `CacheSource::cost`. A
+    /// cache-wide ceiling, not split across shards: an item's admission is
+    /// judged against this whole budget regardless of which shard its id
+    /// happens to hash into.
+    capacity: u64,
+    /// Sum of the costs of every item currently resident, across every
+    /// shard. Locked nested *inside* a shard's own lock (see `Shard::insert`)
+    /// so admission/eviction for one shard never blocks a touch on another.
+    budget: Mutex<u64>,
+    /// Frequency sketch backing `EvictionPolicy::TinyLfu`. Shared across
+    /// shards (behind its own lock) because frequency estimates need to be
+    /// comparable cache-wide, not just within one shard.
+    sketch: Option<Mutex<FrequencySketch>>,
+    /// Coalesces concurrent misses for the same id into a single load.
+    inflight: InflightRegistry<T>,
+    ttl: Ttl,
+    metrics: Metrics,
     src: Box<CacheSource<Value=T>>,
 }
 impl<T: 'static> Cache<T> {
-    pub fn new<Src>(capacity: usize, src: Src) -> Cache<T>
+    /// Construct a `Cache` whose total cost, summed over every resident
+    /// item's `CacheSource::cost`, never exceeds `capacity`. Sources that
+    /// don't override `cost` default every item to cost `1`, so `capacity`
+    /// behaves exactly like a fixed item count.
+    pub fn new<Src>(capacity: u64, src: Src) -> Cache<T>
+        where Src: 'static + CacheSource<Value=T> {
+        Self::with_policy(capacity, src, EvictionPolicy::Lru)
+    }
+    /// Construct a `Cache` using an explicit eviction policy, e.g.
+    /// `EvictionPolicy::TinyLfu` to resist scan-caused thrashing.
+    pub fn with_policy<Src>(
+        capacity: u64,
+        src: Src,
+        policy: EvictionPolicy,
+    ) -> Cache<T>
+        where Src: 'static + CacheSource<Value=T> {
+        Self::with_ttl(capacity, src, policy, Ttl::None)
+    }
+    /// Construct a `Cache` whose items expire per `ttl`, e.g. to memoize
+    /// bearer tokens or rendered pages that shouldn't be served forever.
+    pub fn with_ttl<Src>(
+        capacity: u64,
+        src: Src,
+        policy: EvictionPolicy,
+        ttl: Ttl,
+    ) -> Cache<T>
+        where Src: 'static + CacheSource<Value=T> {
+        let shard_count = default_shard_count(capacity);
+        Self::with_config(capacity, src, policy, shard_count, ttl)
+    }
+    /// Construct a `Cache` with an explicit, power-of-two shard count. Reads
+    /// that hit only ever lock the one shard the id hashes to, so a higher
+    /// count gives less lock contention. The cost budget stays cache-wide,
+    /// but eviction victims are only picked from the incoming id's own shard.
+    pub fn with_shards<Src>(
+        capacity: u64,
+        src: Src,
+        policy: EvictionPolicy,
+        shard_count: usize,
+    ) -> Cache<T>
         where Src: 'static + CacheSource<Value=T> {
+        Self::with_config(capacity, src, policy, shard_count, Ttl::None)
+    }
+    fn with_config<Src>(
+        capacity: u64,
+        src: Src,
+        policy: EvictionPolicy,
+        shard_count: usize,
+        ttl: Ttl,
+    ) -> Cache<T>
+        where Src: 'static + CacheSource<Value=T> {
+        let shard_count = shard_count.next_power_of_two().max(1);
+        let shards = (0..shard_count).map(|_| Shard::new()).collect();
+        let sketch = match policy {
+            EvictionPolicy::Lru => None,
+            EvictionPolicy::TinyLfu => Some(Mutex::new(FrequencySketch::new(capacity as usize))),
+        };
         Cache {
-            cache: Mutex::new(Vec::with_capacity(capacity)),
+            shards: shards,
+            shard_mask: shard_count - 1,
+            capacity: capacity,
+            budget: Mutex::new(0),
+            sketch: sketch,
+            inflight: InflightRegistry::new(),
+            ttl: ttl,
+            metrics: Metrics::new(),
             src: Box::new(src),
         }
     }
@@ -73,63 +194,237 @@ impl<T: 'static> Cache<T> {
     }
 
     fn _get(&self, id: &str, create: bool) -> Result<Arc<CacheItem<T>>> {
-        // Not intended to introduce too much complexity.
-        let mut cache = self.cache.lock()
-            .map_err(|_| Error::internal(ERR_POISONED_THREAD))?;
-        if let Some(pos) = cache.iter()
-            .position(|item| item.id() == id) {
-            // Cache found.
-            let arc = cache.remove(pos);
-            cache.insert(0, arc.clone());
-            return Ok(arc)
-        } else {
-            // Requested resource is not yet cached. Load now.
-            let new_item = CacheItem::new(id, self.src.load(id, create)?);
-            let new_arc = Arc::new(new_item);
-            // Not actually caching anything when capacity is 0.
-            if cache.capacity() == 0 {
-                return Ok(new_arc)
+        // Every touch, hit or miss, feeds the frequency sketch so eviction
+        // decisions reflect real access patterns.
+        let incoming_estimate = self.record_access(id)?;
+        let shard = &self.shards[shard_index(id, self.shard_mask)];
+        // Read-hit fast path: a hot-slot hit takes no lock at all; otherwise
+        // the shard's write lock is taken, to promote `id` to the front of
+        // its recency list and keep LRU order true.
+        if let Some(arc) = shard.get(id)? {
+            match self.ttl {
+                Ttl::None => {
+                    self.metrics.record_hit();
+                    return Ok(arc)
+                },
+                Ttl::Fixed(ttl) if !arc.is_expired(ttl) => {
+                    self.metrics.record_hit();
+                    return Ok(arc)
+                },
+                Ttl::Sliding(ttl) if !arc.is_expired(ttl) => {
+                    arc.refresh();
+                    self.metrics.record_hit();
+                    return Ok(arc)
+                },
+                _ => {
+                    // Stale: drop it and fall through to reload below.
+                    self.evict_expired(id, shard, &arc)?;
+                },
+            }
+        }
+        self.metrics.record_miss();
+        // Requested resource is not yet cached. Coalesce concurrent misses
+        // for this id into a single call to the source: only the first
+        // caller runs `load_and_insert`, everyone else waits for its result.
+        self.inflight.join(id, |slot| {
+            self.load_and_insert(id, create, shard, incoming_estimate, slot)
+        })
+    }
+
+    /// Drop an expired item from `shard`, unloading it first if it's dirty.
+    fn evict_expired(
+        &self,
+        id: &str,
+        shard: &Shard<T>,
+        item: &Arc<CacheItem<T>>,
+    ) -> Result<()> {
+        shard.remove(id, &self.budget)?;
+        self.metrics.record_eviction();
+        if item.is_dirty() {
+            let data = item.write()?;
+            match self.src.unload(item.id(), &*data) {
+                Ok(()) => self.metrics.record_unload_success(),
+                Err(err) => {
+                    self.metrics.record_unload_failure();
+                    error!("Unable to unload '{}': {}", id, err);
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Load `id` from the source and insert it into `shard`, evicting a
+    /// victim first if necessary. Only ever run by the leader of an
+    /// in-flight load; see `InflightRegistry::join`.
+    fn load_and_insert(
+        &self,
+        id: &str,
+        create: bool,
+        shard: &Shard<T>,
+        incoming_estimate: u8,
+        slot: &InflightSlot<T>,
+    ) -> Result<Arc<CacheItem<T>>> {
+        self.metrics.record_load();
+        let data = self.src.load(id, create)?;
+        let cost = self.src.cost(id, &data);
+        let new_item = CacheItem::new(id, data);
+        if slot.was_removed() {
+            // `remove` raced this load. Per the pending-state semantics
+            // above, treat the freshly loaded item as if newly (re)created
+            // rather than silently discarding the caller's result.
+            new_item.set_dirty();
+        }
+        let new_arc = Arc::new(new_item);
+        let sketch = &self.sketch;
+        let outcome = shard.insert(id, new_arc.clone(), cost, self.capacity, &self.budget, |recency, map| {
+            if recency.is_empty() {
+                return None;
+            }
+            match *sketch {
+                Some(ref sketch) => {
+                    let sketch = sketch.lock().unwrap();
+                    Self::sample_victim(recency, map, &sketch, incoming_estimate)
+                },
+                // Plain LRU: the tail is always the least recently used.
+                None => Some(recency.len() - 1),
             }
-            // Remove the least-recently-used item from collection.
-            if cache.len() == cache.capacity() {
-                let lru_item = cache.pop().unwrap();
-                // Unload items only when they are dirty.
-                if lru_item.is_dirty() {
-                    let data = lru_item.write()?;
-                    if let Err(err) = self.src.unload(lru_item.id(), &*data) {
+        })?;
+        match outcome {
+            // Unload every evicted item that's dirty. A single costly item
+            // can evict several cheaper ones in one go.
+            InsertOutcome::Inserted(victims) => {
+                self.unload_evicted(id, victims)?;
+                Ok(new_arc)
+            },
+            // Another caller inserted the same id first; use their item.
+            InsertOutcome::AlreadyPresent(arc) => Ok(arc),
+            // The incoming item isn't estimated to be worth more than the
+            // sampled victims, or its cost alone exceeds the cache's whole
+            // budget, or its shard ran out of residents to evict while the
+            // cache-wide budget is still full, so it isn't admitted into the
+            // cache - but any victim
+            // already evicted before that decision is still gone for good
+            // and must still be unloaded if dirty.
+            InsertOutcome::NotAdmitted(victims) => {
+                self.unload_evicted(id, victims)?;
+                Ok(new_arc)
+            },
+        }
+    }
+
+    /// Unload every evicted `victims` that's dirty, recording an eviction for
+    /// each regardless of dirtiness. `id` is only used for logging context.
+    fn unload_evicted(&self, id: &str, victims: Vec<Arc<CacheItem<T>>>) -> Result<()> {
+        for victim in victims {
+            self.metrics.record_eviction();
+            if victim.is_dirty() {
+                let data = victim.write()?;
+                match self.src.unload(victim.id(), &*data) {
+                    Ok(()) => self.metrics.record_unload_success(),
+                    Err(err) => {
+                        self.metrics.record_unload_failure();
                         error!("Unable to unload '{}': {}", id, err);
-                    }   
+                    },
                 }
             }
-            cache.insert(0, new_arc.clone());
-            return Ok(new_arc)
+        }
+        Ok(())
+    }
+
+    /// Record a touch of `id` in the frequency sketch, if the cache was
+    /// constructed with `EvictionPolicy::TinyLfu`.
+    fn record_access(&self, id: &str) -> Result<u8> {
+        match self.sketch {
+            Some(ref sketch) => {
+                let mut sketch = sketch.lock()
+                    .map_err(|_| Error::internal(ERR_POISONED_THREAD))?;
+                Ok(sketch.record_access(id))
+            },
+            None => Ok(0),
+        }
+    }
+
+    /// Sample a handful of resident items from the least-recently-used end
+    /// of a shard's recency list and return the position of the one with the
+    /// lowest frequency estimate, or `None` if `incoming_estimate` doesn't
+    /// beat it.
+    fn sample_victim(
+        recency: &[String],
+        _map: &HashMap<String, (Arc<CacheItem<T>>, u64)>,
+        sketch: &FrequencySketch,
+        incoming_estimate: u8,
+    ) -> Option<usize> {
+        let sample_size = TINY_LFU_SAMPLE_SIZE.min(recency.len());
+        let start = recency.len() - sample_size;
+        let (victim_pos, victim_estimate) = (start..recency.len())
+            .map(|pos| (pos, sketch.estimate(&recency[pos])))
+            .min_by_key(|&(_, estimate)| estimate)
+            .unwrap();
+        if incoming_estimate > victim_estimate {
+            Some(victim_pos)
+        } else {
+            None
         }
     }
 
     /// Remove the object identified by given ID.
     pub fn remove(&self, id: &str) -> Result<()> {
-        let mut cache = self.cache.lock().unwrap();
-        cache.iter()
-            .position(|nid| nid.id() == id)
-            .map(|pos| cache.remove(pos));
+        // If a load for this id is in flight, flag it so the leader restores
+        // the item to `Dirty` instead of acting as though nothing happened.
+        self.inflight.mark_removed(id);
+        let shard = &self.shards[shard_index(id, self.shard_mask)];
+        if shard.remove(id, &self.budget)?.is_some() {
+            self.metrics.record_eviction();
+        }
         self.src.remove(&id)
     }
 
-    /// The maximum number of items can be cached at a same time. Tests only.
+    /// A snapshot of this cache's hit/miss/eviction counters.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// The total cost currently resident across every shard, per
+    /// `CacheSource::cost`.
+    pub fn total_cost(&self) -> u64 {
+        *self.budget.lock().unwrap()
+    }
+
+    /// The total cost this cache can hold at a same time. Tests only.
     #[cfg(test)]
-    pub fn capacity(&self) -> usize {
-        // Only if the thread is poisoned `cache` will be unavailable.
-        self.cache.lock().unwrap().capacity()
+    pub fn capacity(&self) -> u64 {
+        self.capacity
     }
 
     /// Get the number of items cached. Tests only.
     #[cfg(test)]
     pub fn len(&self) -> usize {
-        // Only if the thread is poisoned `cache` will be unavailable.
-        self.cache.lock().unwrap().len()
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+}
+
+/// Pick a sensible default shard count for a cache of the given capacity.
+/// Shard count only affects lock granularity and how victims are sampled,
+/// not the cache's admission budget (that's cache-wide, see `Cache::budget`),
+/// so this is free to under- or over-estimate without breaking the capacity
+/// guarantee. Small caches (the common case in tests and light use) stay
+/// single-sharded so eviction order is exactly as before; larger caches get
+/// sharded for concurrency, capped at `MAX_SHARDS`.
+fn default_shard_count(capacity: u64) -> usize {
+    if capacity < (MIN_CAPACITY_PER_SHARD * 2) as u64 {
+        1
+    } else {
+        ((capacity / MIN_CAPACITY_PER_SHARD as u64) as usize).next_power_of_two().min(MAX_SHARDS)
     }
 }
 
+/// Route an id to a shard index, for a `shard_mask` of `shard_count - 1`.
+fn shard_index(id: &str, shard_mask: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    (hasher.finish() as usize) & shard_mask
+}
+
 /// A source where cache can be generated from.
 pub trait CacheSource: 'static + Send + Sync {
     type Value: 'static;
@@ -151,17 +446,28 @@ pub trait CacheSource: 'static + Send + Sync {
     fn remove(&self, _id: &str) -> Result<()> {
         Ok(())
     }
+    /// The cost charged against a `Cache`'s capacity while `obj` is resident,
+    /// e.g. its size in bytes. Defaults to `1`, so a `Cache` whose source
+    /// doesn't override this behaves exactly like a fixed item count.
+    fn cost(&self, _id: &str, _obj: &Self::Value) -> u64 {
+        1
+    }
 }
 impl<T: 'static> Drop for Cache<T> {
     /// Implement drop so that modified cached data can be returned to source
     /// properly.
     fn drop(&mut self) {
-        let mut lock = self.cache.lock().unwrap();
-        while let Some(item) = lock.pop() {
-            if !item.is_dirty() { continue }
-            let guard = item.write().unwrap();
-            if let Err(err) = self.src.unload(item.id(), &guard) {
-                warn!("Unable to unload '{}': {}", item.id(), err);
+        for shard in self.shards.iter_mut() {
+            for item in shard.drain(&self.budget) {
+                if !item.is_dirty() { continue }
+                let guard = item.write().unwrap();
+                match self.src.unload(item.id(), &guard) {
+                    Ok(()) => self.metrics.record_unload_success(),
+                    Err(err) => {
+                        self.metrics.record_unload_failure();
+                        warn!("Unable to unload '{}': {}", item.id(), err);
+                    },
+                }
             }
         }
     }
@@ -169,6 +475,10 @@ impl<T: 'static> Drop for Cache<T> {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Barrier};
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::thread;
+    use std::time::Duration;
     use writium::prelude::*;
     // `bool` controls always fail.
     struct TestSource(bool);
@@ -238,4 +548,118 @@ mod tests {
         assert!(cache.len() == 0);
         assert!(cache.remove("0").is_ok());
     }
+
+    /// A source that counts how many times `load` is actually called, and
+    /// sleeps briefly inside it so two racing callers are very likely to
+    /// both observe a miss before either one finishes loading.
+    struct CountingSource(Arc<AtomicUsize>);
+    impl super::CacheSource for CountingSource {
+        type Value = u32;
+        fn load(&self, _id: &str, _create: bool) -> Result<Self::Value> {
+            self.0.fetch_add(1, AtomicOrdering::SeqCst);
+            thread::sleep(Duration::from_millis(50));
+            Ok(42)
+        }
+    }
+
+    #[test]
+    fn test_concurrent_miss_coalesces_into_one_load() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let cache = Arc::new(super::Cache::new(10, CountingSource(loads.clone())));
+        let barrier = Arc::new(Barrier::new(2));
+        let handles: Vec<_> = (0..2).map(|_| {
+            let cache = cache.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                cache.get("shared")
+            })
+        }).collect();
+        for handle in handles {
+            let item = handle.join().unwrap().unwrap();
+            assert_eq!(*item.read().unwrap(), 42);
+        }
+        assert_eq!(loads.load(AtomicOrdering::SeqCst), 1);
+    }
+
+    /// A source whose loaded value is the number of times it's been called
+    /// so far, so a test can tell whether a `get` actually reloaded.
+    struct ReloadingSource(Arc<AtomicUsize>);
+    impl super::CacheSource for ReloadingSource {
+        type Value = usize;
+        fn load(&self, _id: &str, _create: bool) -> Result<Self::Value> {
+            Ok(self.0.fetch_add(1, AtomicOrdering::SeqCst))
+        }
+    }
+
+    #[test]
+    fn test_ttl_expiry_triggers_reload() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let cache = super::Cache::with_ttl(
+            10,
+            ReloadingSource(loads.clone()),
+            super::EvictionPolicy::Lru,
+            super::Ttl::Fixed(Duration::from_millis(20)),
+        );
+        let first = cache.get("a").unwrap();
+        assert_eq!(*first.read().unwrap(), 0);
+        // Still fresh: no reload.
+        assert_eq!(*cache.get("a").unwrap().read().unwrap(), 0);
+        thread::sleep(Duration::from_millis(30));
+        let second = cache.get("a").unwrap();
+        assert_eq!(*second.read().unwrap(), 1);
+    }
+
+    /// A source whose cost is read back out of its own loaded value. Ids are
+    /// formatted `"<cost>-<unique>"` so a test can pick exactly what each
+    /// item charges against the cache's budget.
+    struct CostSource;
+    impl super::CacheSource for CostSource {
+        type Value = u64;
+        fn load(&self, id: &str, _create: bool) -> Result<Self::Value> {
+            Ok(id.split('-').next().unwrap().parse().unwrap())
+        }
+        fn cost(&self, _id: &str, obj: &Self::Value) -> u64 {
+            *obj
+        }
+    }
+
+    #[test]
+    fn test_cost_override_evicts_several_small_items() {
+        let cache = super::Cache::with_policy(10, CostSource, super::EvictionPolicy::Lru);
+        for i in 0..5 {
+            assert!(cache.get(&format!("2-{}", i)).is_ok());
+        }
+        assert_eq!(cache.len(), 5);
+        assert_eq!(cache.total_cost(), 10);
+        // One item costing 6 doesn't fit alongside 10 already-resident cost;
+        // it must evict several of the cost-2 items (not just one) to fit.
+        assert!(cache.get("6-big").is_ok());
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.total_cost(), 10);
+    }
+
+    /// A source that always succeeds, loading `id` parsed as its value.
+    struct AlwaysSource;
+    impl super::CacheSource for AlwaysSource {
+        type Value = u32;
+        fn load(&self, id: &str, _create: bool) -> Result<Self::Value> {
+            Ok(id.parse().unwrap())
+        }
+    }
+
+    #[test]
+    fn test_metrics_reflect_scripted_sequence() {
+        let cache = super::Cache::new(2, AlwaysSource);
+        assert!(cache.get("0").is_ok()); // miss, load
+        assert!(cache.get("0").is_ok()); // hit
+        assert!(cache.get("1").is_ok()); // miss, load
+        assert!(cache.get("2").is_ok()); // miss, load, evicts "0"
+        assert!(cache.remove("1").is_ok()); // explicit eviction
+        let snapshot = cache.metrics();
+        assert_eq!(snapshot.hits, 1);
+        assert_eq!(snapshot.misses, 3);
+        assert_eq!(snapshot.loads, 3);
+        assert_eq!(snapshot.evictions, 2);
+    }
 }
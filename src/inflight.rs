@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use writium::prelude::*;
+use item::CacheItem;
+
+const ERR_LOADER_PANICKED: &str =
+    "Concurrent loader of this id panicked before producing a result.";
+
+/// The leader's shareable result: its loaded item, or the error it failed
+/// with. The error is wrapped in an `Arc` purely so every waiter can clone
+/// the shared slot cheaply; the original `Error` (status, message, and all)
+/// is preserved as-is rather than reformatted, so a follower sees exactly
+/// the same error the leader did.
+type SlotResult<T> = ::std::result::Result<Arc<CacheItem<T>>, Arc<Error>>;
+
+/// Per-id single-flight slot. The first caller to miss on an id becomes its
+/// leader and actually loads it; every other concurrent caller for the same
+/// id becomes a follower and waits here for the leader's result instead of
+/// calling into the `CacheSource` itself.
+pub(crate) struct InflightSlot<T: 'static> {
+    result: Mutex<Option<SlotResult<T>>>,
+    condvar: Condvar,
+    /// Set when `Cache::remove` is called for this id while its load is
+    /// still in flight, so the leader can mark the freshly loaded item dirty
+    /// per the "restored to Dirty" pending-state semantics.
+    removed: AtomicBool,
+}
+impl<T: 'static> InflightSlot<T> {
+    fn new() -> InflightSlot<T> {
+        InflightSlot {
+            result: Mutex::new(None),
+            condvar: Condvar::new(),
+            removed: AtomicBool::new(false),
+        }
+    }
+
+    fn mark_removed(&self) {
+        self.removed.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether `mark_removed` was called before the load finished.
+    pub fn was_removed(&self) -> bool {
+        self.removed.load(Ordering::SeqCst)
+    }
+
+    /// Block until the leader records a result, then return a clone of it.
+    fn wait(&self) -> Result<Arc<CacheItem<T>>> {
+        let mut result = self.result.lock().unwrap();
+        while result.is_none() {
+            result = self.condvar.wait(result).unwrap();
+        }
+        match *result {
+            Some(Ok(ref item)) => Ok(item.clone()),
+            // Clone the leader's original error rather than reformatting it
+            // through `Error::internal`, so a follower sees the same status
+            // (e.g. a 404) the leader did instead of always a 500.
+            Some(Err(ref err)) => Err((**err).clone()),
+            None => unreachable!(),
+        }
+    }
+}
+
+/// Ensures an in-flight slot is always finished, even if its leader panics:
+/// waiters would otherwise block on the condvar forever.
+struct FinishOnDrop<'a, T: 'static> {
+    registry: &'a InflightRegistry<T>,
+    id: &'a str,
+    slot: &'a Arc<InflightSlot<T>>,
+}
+impl<'a, T: 'static> Drop for FinishOnDrop<'a, T> {
+    fn drop(&mut self) {
+        {
+            let mut result = self.slot.result.lock().unwrap();
+            if result.is_none() {
+                // The leader panicked before recording a result. Fail
+                // followers instead of leaving them blocked forever.
+                *result = Some(Err(Arc::new(Error::internal(ERR_LOADER_PANICKED))));
+            }
+        }
+        self.registry.slots.lock().unwrap().remove(self.id);
+        self.slot.condvar.notify_all();
+    }
+}
+
+/// Registry of in-flight loads, keyed by id, used to coalesce concurrent
+/// misses for the same id into a single call to the `CacheSource`.
+pub(crate) struct InflightRegistry<T: 'static> {
+    slots: Mutex<HashMap<String, Arc<InflightSlot<T>>>>,
+}
+impl<T: 'static> InflightRegistry<T> {
+    pub fn new() -> InflightRegistry<T> {
+        InflightRegistry { slots: Mutex::new(HashMap::new()) }
+    }
+
+    /// Mark `id` as removed, if a load for it is currently in flight.
+    pub fn mark_removed(&self, id: &str) {
+        if let Some(slot) = self.slots.lock().unwrap().get(id) {
+            slot.mark_removed();
+        }
+    }
+
+    /// Join the in-flight load for `id`: the first caller runs `body` and
+    /// becomes the leader for every other concurrent caller, who instead
+    /// block on the leader's result.
+    pub fn join<F>(&self, id: &str, body: F) -> Result<Arc<CacheItem<T>>>
+        where F: FnOnce(&InflightSlot<T>) -> Result<Arc<CacheItem<T>>> {
+        let (slot, is_leader) = {
+            let mut slots = self.slots.lock().unwrap();
+            if let Some(slot) = slots.get(id) {
+                (slot.clone(), false)
+            } else {
+                let slot = Arc::new(InflightSlot::new());
+                slots.insert(id.to_owned(), slot.clone());
+                (slot, true)
+            }
+        };
+        if !is_leader {
+            // Someone else is already loading this id; wait for their
+            // result instead of calling the source ourselves. The registry
+            // lock above is already released, so we won't block the leader
+            // from finishing and cleaning up the slot.
+            return slot.wait()
+        }
+        let _guard = FinishOnDrop { registry: self, id: id, slot: &slot };
+        let outcome = body(&slot);
+        let shared = match outcome {
+            Ok(ref item) => Ok(item.clone()),
+            // Preserve the real error (status included) for followers,
+            // rather than discarding it behind a generic `Error::internal`.
+            Err(ref err) => Err(Arc::new(err.clone())),
+        };
+        *slot.result.lock().unwrap() = Some(shared);
+        outcome
+    }
+}
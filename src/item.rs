@@ -1,5 +1,6 @@
-use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use writium::prelude::*;
 
 const ERR_POISONED_THREAD: &str = "Current thread is poisoned.";
@@ -9,6 +10,8 @@ pub struct CacheItem<T: 'static> {
     id: String,
     data: RwLock<T>,
     is_dirty: AtomicBool,
+    /// When this item was loaded, or last refreshed by a sliding TTL.
+    touched: Mutex<Instant>,
 }
 impl<T: 'static> CacheItem<T> {
     pub fn new(id: &str, data: T) -> CacheItem<T> {
@@ -16,6 +19,7 @@ impl<T: 'static> CacheItem<T> {
             id: id.to_owned(),
             data: RwLock::new(data),
             is_dirty: AtomicBool::new(false),
+            touched: Mutex::new(Instant::now()),
         }
     }
 
@@ -46,4 +50,14 @@ impl<T: 'static> CacheItem<T> {
     pub(crate) fn set_dirty(&self) {
         self.is_dirty.store(true, Ordering::Release)
     }
+
+    /// Whether this item hasn't been touched (loaded, or refreshed under a
+    /// sliding TTL) for at least `ttl`.
+    pub(crate) fn is_expired(&self, ttl: Duration) -> bool {
+        self.touched.lock().unwrap().elapsed() >= ttl
+    }
+    /// Reset the touch timestamp to now, used by a sliding TTL on access.
+    pub(crate) fn refresh(&self) {
+        *self.touched.lock().unwrap() = Instant::now();
+    }
 }
@@ -8,6 +8,12 @@ pub use item::CacheItem;
 
 mod cache;
 mod dumb;
+mod hot;
+mod inflight;
+mod metrics;
+mod policy;
+mod shard;
 
-pub use cache::{Cache, CacheSource};
+pub use cache::{Cache, CacheSource, EvictionPolicy, Ttl};
 pub use dumb::DumbCacheSource;
+pub use metrics::MetricsSnapshot;
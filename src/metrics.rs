@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Running counters for a `Cache`'s hit/miss/load/eviction/unload activity.
+/// Updated lock-free from any thread, so counting never adds lock contention
+/// of its own, not even on a hot-slot hit that takes no shard lock.
+pub(crate) struct Metrics {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    loads: AtomicU64,
+    evictions: AtomicU64,
+    unload_successes: AtomicU64,
+    unload_failures: AtomicU64,
+}
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            loads: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            unload_successes: AtomicU64::new(0),
+            unload_failures: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn record_load(&self) {
+        self.loads.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn record_eviction(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn record_unload_success(&self) {
+        self.unload_successes.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn record_unload_failure(&self) {
+        self.unload_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            loads: self.loads.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            unload_successes: self.unload_successes.load(Ordering::Relaxed),
+            unload_failures: self.unload_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A point-in-time snapshot of a `Cache`'s hit/miss/load/eviction/unload
+/// counters, returned by `Cache::metrics`.
+#[derive(Clone, Copy, Debug)]
+pub struct MetricsSnapshot {
+    /// Number of `get`/`create` calls served from the cache without calling
+    /// into the `CacheSource`.
+    pub hits: u64,
+    /// Number of `get`/`create` calls that had to load from the
+    /// `CacheSource`, whether because the id wasn't cached or its item had
+    /// expired.
+    pub misses: u64,
+    /// Number of times `CacheSource::load` was actually called, whether or
+    /// not it succeeded.
+    pub loads: u64,
+    /// Number of resident items removed from the cache, whether to make room
+    /// under the cost budget, because their TTL expired, or explicitly via
+    /// `Cache::remove`.
+    pub evictions: u64,
+    /// Number of times a dirty item was unloaded to its `CacheSource`
+    /// successfully, whether on eviction or when the owning `Cache` is
+    /// dropped.
+    pub unload_successes: u64,
+    /// Number of times unloading a dirty item to its `CacheSource` returned
+    /// an error, whether on eviction or when the owning `Cache` is dropped.
+    pub unload_failures: u64,
+}
+impl MetricsSnapshot {
+    /// The fraction of `get`/`create` calls served from the cache, in
+    /// `[0, 1]`. `0.0` if there have been no requests yet.
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
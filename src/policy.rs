@@ -0,0 +1,150 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Number of independent hashed rows in the Count-Min Sketch. More rows
+/// reduce the chance of hash collisions inflating an estimate, at the cost of
+/// more memory and more hashing per touch.
+const SKETCH_ROWS: usize = 4;
+/// Counters saturate at this value, mirroring the usual 4-bit counters used
+/// by TinyLFU implementations.
+const MAX_COUNTER: u8 = 15;
+/// Upper bound on the sketch's row width (and so the doorkeeper's size too),
+/// regardless of how large the cache's `capacity` is. `capacity` is an
+/// opaque cost unit (e.g. bytes), not necessarily an item count, so sizing
+/// the sketch directly off it could allocate megabytes of counters for a
+/// cache whose *item* count is actually tiny. 65536 entries per row (4 rows
+/// of `u8` plus a same-sized `bool` doorkeeper) is already generous for any
+/// realistic working set and caps the sketch at a few hundred KB.
+const MAX_WIDTH: usize = 1 << 16;
+
+/// Approximates how often an item id has been accessed, without keeping an
+/// exact per-id counter around forever. Used by `EvictionPolicy::TinyLfu` to
+/// decide whether an incoming item deserves to evict a resident one.
+///
+/// A small doorkeeper bloom filter absorbs the first touch of an id so that
+/// one-off, scan-like accesses never reach (and pollute) the sketch. Once an
+/// id has been seen twice, further touches increment its estimate in the
+/// sketch. Counters are periodically halved so that the estimate reflects
+/// recent access patterns rather than all-time totals.
+pub(crate) struct FrequencySketch {
+    rows: Vec<Vec<u8>>,
+    width_mask: usize,
+    doorkeeper: Vec<bool>,
+    additions: usize,
+    sample_size: usize,
+}
+impl FrequencySketch {
+    /// Size the sketch for a cache of the given `capacity`. `capacity` is
+    /// taken as a rough upper bound on item count for sizing purposes only;
+    /// the row width is capped at `MAX_WIDTH` so a cache whose `capacity` is
+    /// actually an opaque cost unit (e.g. bytes) doesn't blow up the
+    /// sketch's memory far beyond what its real item count would need.
+    pub fn new(capacity: usize) -> FrequencySketch {
+        let width = capacity.clamp(16, MAX_WIDTH).next_power_of_two();
+        FrequencySketch {
+            rows: (0..SKETCH_ROWS).map(|_| vec![0u8; width]).collect(),
+            width_mask: width - 1,
+            doorkeeper: vec![false; width],
+            additions: 0,
+            sample_size: capacity.max(1) * 10,
+        }
+    }
+
+    /// Record a `get`/`create` touch of `id` and return its resulting
+    /// frequency estimate. The first touch of an id is absorbed by the
+    /// doorkeeper and doesn't increment the sketch.
+    pub fn record_access(&mut self, id: &str) -> u8 {
+        let door_idx = self.index_for(id, SKETCH_ROWS);
+        if !self.doorkeeper[door_idx] {
+            self.doorkeeper[door_idx] = true;
+            return 1;
+        }
+        for row in 0..SKETCH_ROWS {
+            let idx = self.index_for(id, row);
+            if self.rows[row][idx] < MAX_COUNTER {
+                self.rows[row][idx] += 1;
+            }
+        }
+        self.additions += 1;
+        if self.additions >= self.sample_size {
+            self.age();
+        }
+        self.estimate(id)
+    }
+
+    /// Read the current frequency estimate of `id` without recording a touch.
+    pub fn estimate(&self, id: &str) -> u8 {
+        let min_count = (0..SKETCH_ROWS)
+            .map(|row| self.rows[row][self.index_for(id, row)])
+            .min()
+            .unwrap_or(0);
+        if self.doorkeeper[self.index_for(id, SKETCH_ROWS)] {
+            min_count.max(1)
+        } else {
+            0
+        }
+    }
+
+    /// Halve every counter and clear the doorkeeper so estimates track recent
+    /// activity instead of accumulating forever.
+    fn age(&mut self) {
+        for row in self.rows.iter_mut() {
+            for counter in row.iter_mut() {
+                *counter >>= 1;
+            }
+        }
+        for bit in self.doorkeeper.iter_mut() {
+            *bit = false;
+        }
+        self.additions = 0;
+    }
+
+    /// Hash `id` for a given sketch row (or the doorkeeper, when `row ==
+    /// SKETCH_ROWS`), folded into the sketch width.
+    fn index_for(&self, id: &str, row: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        id.hash(&mut hasher);
+        (hasher.finish() as usize) & self.width_mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrequencySketch;
+
+    #[test]
+    fn test_fresh_sketch_estimates_zero() {
+        let sketch = FrequencySketch::new(64);
+        assert_eq!(sketch.estimate("never-seen"), 0);
+    }
+
+    #[test]
+    fn test_first_touch_absorbed_by_doorkeeper() {
+        let mut sketch = FrequencySketch::new(64);
+        // The doorkeeper absorbs the first touch: it's reported as seen once
+        // but doesn't yet bump the sketch's counters.
+        assert_eq!(sketch.record_access("a"), 1);
+        assert_eq!(sketch.estimate("a"), 1);
+    }
+
+    #[test]
+    fn test_estimate_increases_with_repeated_access() {
+        let mut sketch = FrequencySketch::new(64);
+        let seen: Vec<u8> = (0..5).map(|_| sketch.record_access("a")).collect();
+        assert_eq!(seen, vec![1, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_aging_halves_counters_and_resets_doorkeeper() {
+        // capacity 1 makes `sample_size` small (10) so a single id's repeated
+        // touches trigger `age` deterministically within this test.
+        let mut sketch = FrequencySketch::new(1);
+        let seen: Vec<u8> = (0..13).map(|_| sketch.record_access("a")).collect();
+        // Counts climb 1..9 as usual, then the access that pushes `additions`
+        // to `sample_size` triggers `age`: counters are halved and the
+        // doorkeeper is cleared, so that same call's estimate drops to 0.
+        // The cycle then resumes from the halved (not zeroed) counters.
+        assert_eq!(seen, vec![1, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 6]);
+    }
+}
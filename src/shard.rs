@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use writium::prelude::*;
+use hot::HotSlot;
+use item::CacheItem;
+
+const ERR_POISONED_THREAD: &str = "Current thread is poisoned.";
+
+/// Outcome of trying to insert a freshly loaded item into a `Shard`.
+pub(crate) enum InsertOutcome<T: 'static> {
+    /// The item was inserted. Carries every item evicted to make room for
+    /// it; a single costly item can evict several cheaper ones.
+    Inserted(Vec<Arc<CacheItem<T>>>),
+    /// Another caller raced us and inserted the same id first; that item is
+    /// returned instead of ours.
+    AlreadyPresent(Arc<CacheItem<T>>),
+    /// The shard declined to admit the new item, e.g. because the eviction
+    /// policy judged every resident candidate more valuable than it, or
+    /// because the item's cost alone exceeds the cache's whole budget.
+    /// Still carries every item evicted before the decision to decline was
+    /// reached: once `select_victim` approves a victim it's already removed
+    /// from the shard, so the caller must unload it the same as it would for
+    /// `Inserted` rather than letting it leak out unaccounted for.
+    NotAdmitted(Vec<Arc<CacheItem<T>>>),
+}
+
+/// One partition of a `Cache`'s storage. Splitting the cache into shards lets
+/// callers touching different ids contend on different locks instead of one
+/// cache-wide lock, and the hashmap lookup replaces the old O(n) linear scan.
+///
+/// A shard doesn't know its own slice of the cache's cost budget: the budget
+/// is a single cache-wide total (see `Cache::budget`), passed in by
+/// reference to `insert`/`remove`/`drain`, so an item's admission is judged
+/// against the cache's whole capacity rather than an arbitrary fraction of
+/// it. Only *which* resident item to evict to free room is still decided
+/// per-shard, from the shard handling the incoming id's own residents.
+pub(crate) struct Shard<T: 'static> {
+    state: RwLock<ShardState<T>>,
+    /// Lock-free snapshot of this shard's most recently touched item, so a
+    /// hit on it never has to take `state`'s lock at all.
+    hot: HotSlot<T>,
+}
+struct ShardState<T: 'static> {
+    map: HashMap<String, (Arc<CacheItem<T>>, u64)>,
+    /// Ids in recency order, most recently touched first: a hit promotes its
+    /// id to the front, same as insertion, so `EvictionPolicy::Lru` evicts by
+    /// true least-recently-used order rather than insertion order.
+    recency: Vec<String>,
+}
+impl<T: 'static> Shard<T> {
+    pub fn new() -> Shard<T> {
+        Shard {
+            state: RwLock::new(ShardState {
+                map: HashMap::new(),
+                recency: Vec::new(),
+            }),
+            hot: HotSlot::new(),
+        }
+    }
+
+    /// Get the number of items resident in this shard. Tests only.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.state.read().unwrap().map.len()
+    }
+
+    /// Try to satisfy a request. A hit on the hot slot never takes a lock at
+    /// all and doesn't need to: the hot slot only ever holds the single most
+    /// recently touched item, so it's already the most-recent end of the
+    /// recency order. Otherwise falls back to a lookup under the shard's
+    /// write lock, promoting the found item to both the front of `recency`
+    /// and the hot slot for next time.
+    pub fn get(&self, id: &str) -> Result<Option<Arc<CacheItem<T>>>> {
+        if let Some(item) = self.hot.load() {
+            if item.id() == id {
+                return Ok(Some(item));
+            }
+        }
+        // A write lock, not a read lock: a hit has to move `id` to the front
+        // of `recency` to keep eviction order true LRU rather than FIFO.
+        let mut state = self.state.write()
+            .map_err(|_| Error::internal(ERR_POISONED_THREAD))?;
+        let found = state.map.get(id).map(|(item, _cost)| item.clone());
+        if found.is_some() {
+            if let Some(pos) = state.recency.iter().position(|rid| rid == id) {
+                if pos != 0 {
+                    let rid = state.recency.remove(pos);
+                    state.recency.insert(0, rid);
+                }
+            }
+        }
+        if let Some(ref item) = found {
+            self.hot.store(Some(item.clone()));
+        }
+        Ok(found)
+    }
+
+    /// Insert a freshly loaded item of the given `cost`, evicting victims
+    /// from this shard first if the cache (whose current total is tracked in
+    /// `budget`, out of its whole `capacity`) doesn't have enough spare room
+    /// for it. `select_victim` inspects the recency list and resident map
+    /// and returns the position of the next item to evict, or `None` to
+    /// reject the newcomer outright; it's called once per eviction needed,
+    /// since a single large item may have to evict several smaller ones.
+    pub fn insert<F>(
+        &self,
+        id: &str,
+        item: Arc<CacheItem<T>>,
+        cost: u64,
+        capacity: u64,
+        budget: &Mutex<u64>,
+        mut select_victim: F,
+    ) -> Result<InsertOutcome<T>>
+        where F: FnMut(&[String], &HashMap<String, (Arc<CacheItem<T>>, u64)>) -> Option<usize> {
+        let mut state = self.state.write()
+            .map_err(|_| Error::internal(ERR_POISONED_THREAD))?;
+        // Someone may have loaded and inserted the same id while we weren't
+        // holding the write lock yet.
+        if let Some((existing, _cost)) = state.map.get(id) {
+            return Ok(InsertOutcome::AlreadyPresent(existing.clone()));
+        }
+        if cost > capacity {
+            return Ok(InsertOutcome::NotAdmitted(Vec::new()));
+        }
+        let mut total = budget.lock().map_err(|_| Error::internal(ERR_POISONED_THREAD))?;
+        let mut evicted = Vec::new();
+        while *total + cost > capacity {
+            match select_victim(&state.recency, &state.map) {
+                Some(pos) => {
+                    let victim_id = state.recency.remove(pos);
+                    if let Some((victim, victim_cost)) = state.map.remove(&victim_id) {
+                        *total -= victim_cost;
+                        self.hot.clear_if(&victim_id);
+                        evicted.push(victim);
+                    }
+                },
+                // Every candidate examined so far was rejected; whatever was
+                // already evicted this call is gone from the shard for good
+                // and must still be handed back for unloading.
+                None => return Ok(InsertOutcome::NotAdmitted(evicted)),
+            }
+        }
+        state.recency.insert(0, id.to_owned());
+        state.map.insert(id.to_owned(), (item.clone(), cost));
+        *total += cost;
+        self.hot.store(Some(item));
+        Ok(InsertOutcome::Inserted(evicted))
+    }
+
+    /// Remove an item by id, whether or not it's actually resident.
+    pub fn remove(&self, id: &str, budget: &Mutex<u64>) -> Result<Option<Arc<CacheItem<T>>>> {
+        let mut state = self.state.write()
+            .map_err(|_| Error::internal(ERR_POISONED_THREAD))?;
+        if let Some(pos) = state.recency.iter().position(|rid| rid == id) {
+            state.recency.remove(pos);
+        }
+        self.hot.clear_if(id);
+        if let Some((item, cost)) = state.map.remove(id) {
+            *budget.lock().map_err(|_| Error::internal(ERR_POISONED_THREAD))? -= cost;
+            Ok(Some(item))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Drain every resident item, e.g. when the owning `Cache` is dropped.
+    pub fn drain(&mut self, budget: &Mutex<u64>) -> Vec<Arc<CacheItem<T>>> {
+        let state = self.state.get_mut().unwrap();
+        state.recency.clear();
+        self.hot.store(None);
+        let drained: Vec<(Arc<CacheItem<T>>, u64)> = state.map.drain().map(|(_, v)| v).collect();
+        let mut total = budget.lock().unwrap();
+        for &(_, cost) in drained.iter() {
+            *total -= cost;
+        }
+        drained.into_iter().map(|(item, _)| item).collect()
+    }
+}